@@ -0,0 +1,474 @@
+//! Procedural macro crate providing `#[derive(SqliteTable)]`.
+//!
+//! The `sqlite_from_struct!` declarative macro in the main crate re-emits
+//! its struct with this derive attached, so both share this codegen. The
+//! derive parses the struct through `syn` and resolves each field's real
+//! `syn::Type` by its last path segment (so a fully-qualified path like
+//! `std::string::String` maps to `TEXT`) rather than comparing
+//! `stringify!`'d text. It also works on structs defined anywhere,
+//! including ones with generics, since it doesn't require wrapping the
+//! struct definition inside a macro invocation.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, ExprLit, Fields, GenericArgument, Lit, Meta, PathArguments, Token, Type};
+
+/// Maps a resolved type identifier (e.g. `"String"`, `"i64"`) to the SQLite
+/// column affinity that best represents it. Unrecognized types default to
+/// `TEXT`.
+fn map_sql_type(ident: &str) -> &'static str {
+    match ident {
+        "i32" | "i64" | "u32" | "u64" | "isize" | "usize" => "INTEGER",
+        "f32" | "f64" => "REAL",
+        "String" | "str" => "TEXT",
+        "bool" => "INTEGER", // SQLite uses 0 for false, 1 for true
+        "Vec" => "BLOB", // any Vec<_>; the element type is not checked
+        _ => "TEXT", // Default to TEXT for unknown types
+    }
+}
+
+/// If `ty` is `Option<Inner>`, returns `Inner`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    args.args.iter().find_map(|arg| match arg {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    })
+}
+
+/// Resolves a field's Rust type to its SQLite column affinity, and whether
+/// the column should allow NULL (true for `Option<_>` fields).
+fn resolve_sql_type(ty: &Type) -> (&'static str, bool) {
+    if let Some(inner) = option_inner(ty) {
+        let (sql_type, _) = resolve_sql_type(inner);
+        return (sql_type, true);
+    }
+
+    match ty {
+        Type::Path(type_path) => {
+            let segment = type_path
+                .path
+                .segments
+                .last()
+                .expect("a type path always has at least one segment");
+            (map_sql_type(&segment.ident.to_string()), false)
+        }
+        Type::Reference(type_ref) => resolve_sql_type(&type_ref.elem),
+        other => {
+            // A type that reached this derive through another macro's `:ty`
+            // fragment (as `sqlite_from_struct!` does before re-emitting its
+            // struct with this derive attached) arrives as an opaque token
+            // group that `syn` can't walk structurally, even though it's an
+            // ordinary type once rendered back to source text. Re-parse
+            // that text to recover the real `syn::Type` instead of quietly
+            // falling back to TEXT for every field.
+            match syn::parse_str::<Type>(&quote::quote!(#other).to_string()) {
+                Ok(reparsed) if !matches!(reparsed, Type::Verbatim(_)) => {
+                    resolve_sql_type(&reparsed)
+                }
+                _ => ("TEXT", false),
+            }
+        }
+    }
+}
+
+/// Collects the `Meta` items out of every `#[sql(...)]` attribute attached
+/// to a struct or field, e.g. `#[sql(unique, default = "pending")]` yields
+/// one `Meta::Path` and one `Meta::NameValue`.
+fn parse_sql_attrs(attrs: &[syn::Attribute]) -> Vec<Meta> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("sql"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .collect()
+}
+
+/// Whether `#[sql(name)]` (a bare path, no value) is present.
+fn has_flag(metas: &[Meta], name: &str) -> bool {
+    metas
+        .iter()
+        .any(|meta| matches!(meta, Meta::Path(path) if path.is_ident(name)))
+}
+
+/// The literal expression bound to `#[sql(name = ...)]`, if present.
+fn find_value<'a>(metas: &'a [Meta], name: &str) -> Option<&'a Expr> {
+    metas.iter().find_map(|meta| match meta {
+        Meta::NameValue(nv) if nv.path.is_ident(name) => Some(&nv.value),
+        _ => None,
+    })
+}
+
+/// Renders a `#[sql(default = ...)]` expression as the literal that follows
+/// `DEFAULT` in the generated `CREATE TABLE` statement, quoting and
+/// escaping string literals for SQL.
+fn sql_default_literal(expr: &Expr) -> Option<String> {
+    let Expr::Lit(ExprLit { lit, .. }) = expr else {
+        return None;
+    };
+    match lit {
+        Lit::Str(s) => Some(format!("'{}'", s.value().replace('\'', "''"))),
+        Lit::Int(i) => Some(i.base10_digits().to_string()),
+        Lit::Float(f) => Some(f.base10_digits().to_string()),
+        Lit::Bool(b) => Some(if b.value { "1" } else { "0" }.to_string()),
+        _ => None,
+    }
+}
+
+/// The plain string value of `#[sql(table = "...")]`. Unlike
+/// `sql_default_literal`, this isn't rendered as a quoted SQL literal — the
+/// table name is spliced directly into identifier position in the
+/// generated SQL, not a value position, so it needs the raw string rather
+/// than something that would need unquoting again.
+fn sql_table_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => s.value(),
+        _ => panic!("#[sql(table = ...)] requires a string literal"),
+    }
+}
+
+#[proc_macro_derive(SqliteTable, attributes(sql))]
+pub fn derive_sqlite_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+
+    let Data::Struct(data) = &input.data else {
+        panic!("#[derive(SqliteTable)] only supports structs");
+    };
+    let Fields::Named(fields) = &data.fields else {
+        panic!("#[derive(SqliteTable)] requires named fields");
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // Derive the table name from the struct name, unless the struct
+    // carries an explicit `#[sql(table = "...")]`.
+    let struct_metas = parse_sql_attrs(&input.attrs);
+    let table_name = find_value(&struct_metas, "table")
+        .map(sql_table_name)
+        .unwrap_or_else(|| format!("{}s", struct_name.to_string().to_lowercase()));
+
+    // First pass: see whether any field declares an explicit primary key,
+    // so the implicit `id` rule can be suppressed.
+    let explicit_primary_key_ident = fields.named.iter().find_map(|field| {
+        if has_flag(&parse_sql_attrs(&field.attrs), "primary_key") {
+            field.ident.clone()
+        } else {
+            None
+        }
+    });
+    let has_explicit_primary_key = explicit_primary_key_ident.is_some();
+
+    // The field that drives `find_by_id`/`update`/`delete`: whichever field
+    // carries `#[sql(primary_key)]`, or `id` if none does. Structs with a
+    // natural key (no `id` field at all) must declare `primary_key`
+    // explicitly, since there's otherwise nothing to key these methods on.
+    let primary_key_ident = explicit_primary_key_ident
+        .or_else(|| {
+            fields
+                .named
+                .iter()
+                .find(|field| field.ident.as_ref().is_some_and(|ident| ident == "id"))
+                .and_then(|field| field.ident.clone())
+        })
+        .unwrap_or_else(|| {
+            panic!(
+                "#[derive(SqliteTable)] requires an `id` field or an explicit #[sql(primary_key)] field"
+            )
+        });
+    // Filled in below once the loop reaches `primary_key_ident`; used to
+    // decide whether the key is autoincrementing (and so left out of
+    // `insert`) or a natural key that the caller must supply.
+    let mut primary_key_sql_type = "INTEGER";
+
+    let mut column_defs = Vec::new();
+    let mut field_idents = Vec::new();
+    // `(column_name, "col type [NOT NULL] [DEFAULT ...]")` for every field
+    // except the primary key, reused by `migrate` to add missing columns
+    // later (a primary key column can't be added via `ALTER TABLE ADD
+    // COLUMN` anyway).
+    let mut migration_defs: Vec<(String, String)> = Vec::new();
+    for field in &fields.named {
+        let ident = field
+            .ident
+            .clone()
+            .expect("Fields::Named only yields named fields");
+        let column_name = ident.to_string();
+        let field_metas = parse_sql_attrs(&field.attrs);
+
+        let is_primary_key = has_flag(&field_metas, "primary_key");
+        let is_unique = has_flag(&field_metas, "unique");
+        let is_not_null = has_flag(&field_metas, "not_null");
+        let default_value = find_value(&field_metas, "default").and_then(sql_default_literal);
+
+        let (sql_type, is_nullable) = resolve_sql_type(&field.ty);
+
+        if ident == primary_key_ident {
+            primary_key_sql_type = sql_type;
+        }
+
+        let mut column_def = format!("{} {}", column_name, sql_type);
+        if is_primary_key {
+            column_def.push_str(" PRIMARY KEY");
+            if sql_type == "INTEGER" {
+                column_def.push_str(" AUTOINCREMENT");
+            } else {
+                // Only an INTEGER PRIMARY KEY is an implicit alias for
+                // SQLite's rowid (and so implicitly non-NULL); every other
+                // type needs NOT NULL spelled out or it can silently hold
+                // NULL, defeating the point of a primary key.
+                column_def.push_str(" NOT NULL");
+            }
+        } else if column_name == "id" && sql_type == "INTEGER" && !has_explicit_primary_key {
+            column_def.push_str(" PRIMARY KEY AUTOINCREMENT");
+        } else if is_not_null || !is_nullable {
+            column_def.push_str(" NOT NULL");
+        }
+
+        if is_unique && !is_primary_key {
+            column_def.push_str(" UNIQUE");
+        }
+
+        if let Some(default) = &default_value {
+            column_def.push_str(&format!(" DEFAULT {}", default));
+        }
+
+        if ident != primary_key_ident {
+            // SQLite refuses `ADD COLUMN ... NOT NULL` without a DEFAULT,
+            // since existing rows would have nothing to fill it with.
+            let mut migration_def = format!("{} {}", column_name, sql_type);
+            if let Some(default) = &default_value {
+                if is_not_null || !is_nullable {
+                    migration_def.push_str(" NOT NULL");
+                }
+                migration_def.push_str(&format!(" DEFAULT {}", default));
+            }
+            migration_defs.push((column_name.clone(), migration_def));
+        }
+
+        column_defs.push(column_def);
+        field_idents.push(ident);
+    }
+
+    let create_sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n    {}\n);",
+        table_name,
+        column_defs.join(",\n    ")
+    );
+
+    let primary_key_column = primary_key_ident.to_string();
+    // An INTEGER primary key autoincrements, so it's left out of `insert`
+    // and filled in by SQLite; any other primary key is a natural key the
+    // caller must supply, so it stays in the insert column list.
+    let primary_key_is_autoincrement = primary_key_sql_type == "INTEGER";
+
+    // Named placeholders (`:col`) instead of positional (`?N`) mean a
+    // struct can grow or reorder fields without misaligning the bound
+    // values, unlike a positional list built in parallel by hand.
+    let insert_idents: Vec<_> = field_idents
+        .iter()
+        .filter(|ident| !(primary_key_is_autoincrement && **ident == primary_key_ident))
+        .cloned()
+        .collect();
+    let insert_columns = insert_idents
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert_placeholders = insert_idents
+        .iter()
+        .map(|ident| format!(":{}", ident))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table_name, insert_columns, insert_placeholders
+    );
+    let insert_named_params = insert_idents.iter().map(|ident| {
+        let name = format!(":{}", ident);
+        quote! { (#name, &self.#ident as &dyn rusqlite::ToSql) }
+    });
+
+    // The primary key itself is never part of the `SET` clause — `update`
+    // matches a row by its key, it doesn't change it.
+    let update_idents: Vec<_> = field_idents
+        .iter()
+        .filter(|ident| **ident != primary_key_ident)
+        .cloned()
+        .collect();
+    let update_set_clause = update_idents
+        .iter()
+        .map(|ident| format!("{} = :{}", ident, ident))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let update_sql = format!(
+        "UPDATE {} SET {} WHERE {} = :{}",
+        table_name, update_set_clause, primary_key_column, primary_key_column
+    );
+    let update_named_params = update_idents
+        .iter()
+        .map(|ident| {
+            let name = format!(":{}", ident);
+            quote! { (#name, &self.#ident as &dyn rusqlite::ToSql) }
+        })
+        .chain(std::iter::once({
+            let name = format!(":{}", primary_key_column);
+            quote! { (#name, &self.#primary_key_ident as &dyn rusqlite::ToSql) }
+        }));
+
+    let select_columns = field_idents
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let select_all_sql = format!("SELECT {} FROM {}", select_columns, table_name);
+    let find_by_id_sql = format!(
+        "SELECT {} FROM {} WHERE {} = ?1",
+        select_columns, table_name, primary_key_column
+    );
+    let delete_sql = format!("DELETE FROM {} WHERE {} = ?1", table_name, primary_key_column);
+
+    // The explicit column list (skipping the autoincrement `id`, same as
+    // `insert`) guards against a CSV whose columns aren't in struct order.
+    let load_csv_sql = format!(
+        "INSERT INTO {0} ({1}) SELECT {1} FROM temp.import",
+        table_name, insert_columns
+    );
+
+    let table_info_sql = format!("PRAGMA table_info({})", table_name);
+    let known_columns = field_idents
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>();
+    let migration_checks = migration_defs.iter().map(|(name, def)| {
+        quote! {
+            if !existing_columns.contains(#name) {
+                let alter_sql = format!("ALTER TABLE {} ADD COLUMN {}", #table_name, #def);
+                conn.execute(&alter_sql, [])?;
+            }
+        }
+    });
+
+    let field_indices: Vec<usize> = (0..field_idents.len()).collect();
+
+    let expanded = quote! {
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            /// Creates the table corresponding to the struct's schema on
+            /// `conn`, if it does not exist yet.
+            pub fn create_table(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+                conn.execute(#create_sql, [])?;
+                Ok(())
+            }
+
+            /// Inserts this instance as a new row in the table.
+            pub fn insert(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+                conn.execute(#insert_sql, &[#(#insert_named_params),*][..])?;
+                Ok(())
+            }
+
+            /// Updates this instance's row in the table, matched by its
+            /// primary key.
+            pub fn update(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+                conn.execute(#update_sql, &[#(#update_named_params),*][..])?;
+                Ok(())
+            }
+
+            /// Loads every row in the table into a `Vec<Self>`.
+            pub fn select_all(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Self>> {
+                let mut stmt = conn.prepare(#select_all_sql)?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(Self {
+                        #(#field_idents: row.get(#field_indices)?,)*
+                    })
+                })?;
+                rows.collect()
+            }
+
+            /// Looks up a single row by its primary key.
+            pub fn find_by_id(conn: &rusqlite::Connection, key: impl rusqlite::ToSql) -> rusqlite::Result<Option<Self>> {
+                let mut stmt = conn.prepare(#find_by_id_sql)?;
+                let mut rows = stmt.query_map(rusqlite::params![key], |row| {
+                    Ok(Self {
+                        #(#field_idents: row.get(#field_indices)?,)*
+                    })
+                })?;
+                match rows.next() {
+                    Some(row) => Ok(Some(row?)),
+                    None => Ok(None),
+                }
+            }
+
+            /// Deletes this instance's row from the table, matched by its
+            /// primary key.
+            pub fn delete(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+                conn.execute(#delete_sql, rusqlite::params![self.#primary_key_ident])?;
+                Ok(())
+            }
+
+            /// Bulk-imports `path`, a CSV file whose header columns match
+            /// this struct's fields, into the table via SQLite's CSV
+            /// virtual table module.
+            pub fn load_csv(conn: &rusqlite::Connection, path: &str) -> rusqlite::Result<()> {
+                rusqlite::vtab::csvtab::load_module(conn)?;
+
+                // Virtual table module arguments are raw SQL text, not bind
+                // parameters, so `path` has to be embedded as a quoted,
+                // escaped string literal rather than passed via `params!`.
+                let quoted_path = format!("'{}'", path.replace('\'', "''"));
+                let create_vtab_sql = format!(
+                    "CREATE VIRTUAL TABLE temp.import USING csv(filename={}, header=YES)",
+                    quoted_path
+                );
+                conn.execute(&create_vtab_sql, [])?;
+
+                let insert_result = conn.execute(#load_csv_sql, []);
+
+                conn.execute("DROP TABLE temp.import", [])?;
+
+                insert_result?;
+                Ok(())
+            }
+
+            /// Brings an existing table up to date with this struct's
+            /// current field list by adding any missing columns.
+            ///
+            /// Columns that exist in the table but no longer have a
+            /// matching field are left in place and logged as a warning,
+            /// since dropping them could discard data silently.
+            pub fn migrate(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+                let mut stmt = conn.prepare(#table_info_sql)?;
+                let existing_columns: std::collections::HashSet<String> = stmt
+                    .query_map([], |row| row.get::<_, String>(1))?
+                    .collect::<rusqlite::Result<_>>()?;
+
+                #(#migration_checks)*
+
+                let known_columns: &[&str] = &[#(#known_columns),*];
+                for existing in &existing_columns {
+                    if !known_columns.contains(&existing.as_str()) {
+                        eprintln!(
+                            "Warning: column '{}' exists in table '{}' but has no matching field on {}; leaving it in place.",
+                            existing, #table_name, stringify!(#struct_name)
+                        );
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
@@ -3,20 +3,32 @@
 // We need the rusqlite crate to interact with SQLite.
 // Add it to your Cargo.toml with:
 // cargo add rusqlite --features "bundled"
-use rusqlite::{Connection, Result};
+// The derived `load_csv` also needs the "csvtab" feature.
+use rusqlite::Connection;
 
-/// A declarative macro that defines a struct and implements a `create_table`
-/// function for it. This function generates an SQLite table based on the
-/// struct's definition.
+// The `SqliteTable` derive lives in the sibling `sqlite_table_derive` crate.
+// Add it to your Cargo.toml with:
+// sqlite_table_derive = { path = "sqlite_table_derive" }
+use sqlite_table_derive::SqliteTable;
+
+/// A declarative macro that defines a struct and implements `create_table`
+/// plus basic CRUD methods for it, based on the struct's field list.
+///
+/// This is kept around for backward compatibility with code written before
+/// `#[derive(SqliteTable)]` existed; it now just re-emits the struct with
+/// that derive attached; see `sqlite_table_derive` for the actual codegen.
+/// The derive understands real `syn::Type`s rather than `stringify!`'d
+/// text, and also works on structs defined outside of a macro invocation,
+/// so prefer it directly for anything new.
 macro_rules! sqlite_from_struct {
     (
-        // Match attributes like `#[derive(Debug)]`
+        // Match attributes like `#[derive(Debug)]` and `#[sql(table = "...")]`.
         $(#[$outer:meta])*
         // Match the struct keyword and its name (e.g., `struct User`)
         struct $struct_name:ident {
             // Match each field within the struct
             $(
-                // Match attributes on fields, if any
+                // Match attributes on fields, if any (including `#[sql(...)]`).
                 $(#[$inner:meta])*
                 // Match the field name and its type (e.g., `id: i32`)
                 $field_name:ident: $field_type:ty
@@ -24,88 +36,21 @@ macro_rules! sqlite_from_struct {
             $(,)? // Allow an optional trailing comma
         }
     ) => {
-        // --- Step 1: Re-create the original struct ---
-        // The macro consumes the struct definition, so we must regenerate it
-        // to make it available to the rest of the program.
+        // Re-create the original struct definition, now with the
+        // `SqliteTable` derive doing the table/CRUD codegen.
         $(#[$outer])*
+        #[derive(SqliteTable)]
         struct $struct_name {
             $(
                 $(#[$inner])*
                 pub $field_name: $field_type,
             )*
         }
-
-        // --- Step 2: Implement the `create_table` function for the struct ---
-        impl $struct_name {
-            /// Connects to an SQLite database and creates a table corresponding
-            /// to the struct's schema.
-            ///
-            /// # Arguments
-            ///
-            /// * `conn` - A reference to an open SQLite connection.
-            ///
-            /// # Returns
-            ///
-            /// * `rusqlite::Result<()>` - An empty Ok result on success, or an Err on failure.
-            pub fn create_table(conn: &Connection) -> Result<()> {
-                // --- Step 3: Build the "CREATE TABLE" SQL string ---
-
-                // Derive table name from struct name (e.g., User -> users)
-                let table_name = stringify!($struct_name).to_lowercase() + "s";
-                let mut create_sql = format!("CREATE TABLE IF NOT EXISTS {} (\n", table_name);
-
-                // Iterate over each field provided in the macro input
-                $(
-                    // Get the field name as a string
-                    let column_name = stringify!($field_name);
-                    // Get the field type as a string
-                    let type_name = stringify!($field_type);
-
-                    // Map Rust types to SQLite column types
-                    let sql_type = match type_name {
-                        "i32" | "i64" | "u32" | "u64" | "isize" | "usize" => "INTEGER",
-                        "f32" | "f64" => "REAL",
-                        "String" | "&str" => "TEXT",
-                        "bool" => "INTEGER", // SQLite uses 0 for false, 1 for true
-                        "Vec<u8>" => "BLOB",
-                        _ => "TEXT", // Default to TEXT for unknown types
-                    };
-
-                    // By convention, if a field is `id: i32`, make it the primary key.
-                    if column_name == "id" && sql_type == "INTEGER" {
-                        create_sql.push_str(&format!("    {} {} PRIMARY KEY AUTOINCREMENT,\n", column_name, sql_type));
-                    } else {
-                        create_sql.push_str(&format!("    {} {},\n", column_name, sql_type));
-                    }
-                )*
-
-                // Remove the last comma and newline if the SQL string is not empty
-                if create_sql.ends_with(",\n") {
-                    create_sql.pop(); // remove \n
-                    create_sql.pop(); // remove ,
-                }
-
-                // Close the SQL statement
-                create_sql.push_str("\n);");
-
-                // Print the generated SQL for verification
-                println!("--- Generated SQL ---");
-                println!("{}", create_sql);
-                println!("---------------------");
-
-                // --- Step 4: Execute the SQL statement ---
-                conn.execute(&create_sql, [])?;
-
-                println!("Successfully created table '{}'.", table_name);
-
-                Ok(())
-            }
-        }
     };
 }
 
 // Use the macro to define a `User` struct.
-// This will create the `User` struct AND the `User::create_table` function.
+// This will create the `User` struct AND its `create_table`/CRUD methods.
 sqlite_from_struct! {
     #[derive(Debug)]
     #[allow(dead_code)]
@@ -131,6 +76,28 @@ sqlite_from_struct! {
     }
 }
 
+// `Order` is defined directly with `#[derive(SqliteTable)]` rather than
+// through `sqlite_from_struct!`. This is the case the derive was added
+// for: a plain struct that lives wherever the rest of the codebase expects
+// it, with no need to wrap its definition inside a macro invocation.
+#[derive(Debug, SqliteTable)]
+#[allow(dead_code)]
+struct Order {
+    id: i32,
+    #[sql(unique)]
+    reference: String,
+    note: Option<String>,
+}
+
+// `Tagged<T>` exercises the generics support the derive was added for: the
+// struct carries a type parameter, so the generated `impl` must repeat it
+// (`impl<T: ...> Tagged<T>`) instead of assuming a concrete, non-generic type.
+#[derive(Debug, SqliteTable)]
+#[allow(dead_code)]
+struct Tagged<T: rusqlite::types::ToSql + rusqlite::types::FromSql> {
+    id: i32,
+    label: T,
+}
 
 fn main() {
     let db_path = "company.db";
@@ -153,4 +120,232 @@ fn main() {
         Ok(_) => println!("Product table creation successful."),
         Err(e) => eprintln!("Error creating product table: {}", e),
     }
+
+    // --- Create the 'orders' table in the same database ---
+    match Order::create_table(&conn) {
+        Ok(_) => println!("Order table creation successful."),
+        Err(e) => eprintln!("Error creating order table: {}", e),
+    }
+
+    // --- Create the 'taggeds' table, instantiated with a concrete T ---
+    match Tagged::<String>::create_table(&conn) {
+        Ok(_) => println!("Tagged table creation successful."),
+        Err(e) => eprintln!("Error creating tagged table: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exercises `#[sql(table = "...")]` and `#[sql(default = ...)]`, neither
+    // of which any struct in `main()`'s demo uses.
+    #[derive(Debug, SqliteTable)]
+    #[allow(dead_code)]
+    #[sql(table = "widget_catalog")]
+    struct Widget {
+        id: i32,
+        #[sql(default = "pending")]
+        status: String,
+    }
+
+    #[test]
+    fn insert_select_all_find_by_id_and_delete_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        User::create_table(&conn).unwrap();
+
+        let alice = User {
+            id: 0,
+            name: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+            age: 30,
+            is_active: true,
+        };
+        alice.insert(&conn).unwrap();
+
+        let stored = User::select_all(&conn).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].name, "Alice");
+        let id = stored[0].id;
+
+        let found = User::find_by_id(&conn, id).unwrap();
+        assert_eq!(found.map(|user| user.email), Some("alice@example.com".to_string()));
+        assert!(User::find_by_id(&conn, id + 1).unwrap().is_none());
+
+        stored.into_iter().next().unwrap().delete(&conn).unwrap();
+        assert!(User::find_by_id(&conn, id).unwrap().is_none());
+    }
+
+    #[test]
+    fn update_writes_every_field_by_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        Order::create_table(&conn).unwrap();
+
+        let order = Order {
+            id: 0,
+            reference: "ORD-1".to_string(),
+            note: None,
+        };
+        order.insert(&conn).unwrap();
+
+        let mut stored = Order::select_all(&conn).unwrap().remove(0);
+        stored.reference = "ORD-1-REVISED".to_string();
+        stored.note = Some("gift wrap".to_string());
+        stored.update(&conn).unwrap();
+
+        let reloaded = Order::find_by_id(&conn, stored.id).unwrap().unwrap();
+        assert_eq!(reloaded.reference, "ORD-1-REVISED");
+        assert_eq!(reloaded.note.as_deref(), Some("gift wrap"));
+    }
+
+    #[test]
+    fn load_csv_bulk_imports_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        User::create_table(&conn).unwrap();
+
+        // `load_csv` projects onto the same columns as `insert` (everything
+        // but the autoincrement `id`), so the header matches those.
+        let csv_path = std::env::temp_dir().join(format!(
+            "sqllite_example_load_csv_test_{:?}.csv",
+            std::thread::current().id()
+        ));
+        std::fs::write(
+            &csv_path,
+            "name,email,age,is_active\n\
+             Bob,bob@example.com,40,1\n\
+             Carol,carol@example.com,25,0\n",
+        )
+        .unwrap();
+
+        let result = User::load_csv(&conn, csv_path.to_str().unwrap());
+        std::fs::remove_file(&csv_path).unwrap();
+        result.unwrap();
+
+        let users = User::select_all(&conn).unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(users.iter().any(|user| user.name == "Bob" && user.age == 40));
+        assert!(users
+            .iter()
+            .any(|user| user.name == "Carol" && !user.is_active));
+    }
+
+    #[test]
+    fn migrate_adds_missing_columns_without_touching_existing_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        // An older schema, missing the `note` column `Order` has since grown.
+        conn.execute(
+            "CREATE TABLE orders (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                reference TEXT NOT NULL UNIQUE
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO orders (reference) VALUES ('ORD-OLD')",
+            [],
+        )
+        .unwrap();
+
+        Order::migrate(&conn).unwrap();
+
+        let mut stmt = conn.prepare("PRAGMA table_info(orders)").unwrap();
+        let columns: std::collections::HashSet<String> = stmt
+            .query_map([], |row| row.get::<_, String>(1))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert!(columns.contains("note"));
+
+        // The pre-existing row survived the migration, with `note` defaulted to NULL.
+        let orders = Order::select_all(&conn).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].reference, "ORD-OLD");
+        assert_eq!(orders[0].note, None);
+
+        // And the table is now usable through the regular derived methods.
+        let order = Order {
+            id: 0,
+            reference: "ORD-NEW".to_string(),
+            note: Some("fragile".to_string()),
+        };
+        order.insert(&conn).unwrap();
+        assert_eq!(Order::select_all(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unique_constraint_rejects_duplicate_value() {
+        let conn = Connection::open_in_memory().unwrap();
+        Order::create_table(&conn).unwrap();
+
+        let first = Order {
+            id: 0,
+            reference: "ORD-DUP".to_string(),
+            note: None,
+        };
+        first.insert(&conn).unwrap();
+
+        let duplicate = Order {
+            id: 0,
+            reference: "ORD-DUP".to_string(),
+            note: None,
+        };
+        let error = duplicate.insert(&conn).unwrap_err().to_string();
+        assert!(
+            error.contains("UNIQUE constraint failed"),
+            "unexpected error: {error}"
+        );
+        assert_eq!(Order::select_all(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn table_and_default_attributes_are_applied() {
+        let conn = Connection::open_in_memory().unwrap();
+        Widget::create_table(&conn).unwrap();
+
+        // `#[sql(table = "...")]` overrides the struct-name-derived table name.
+        let table_exists: i32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'widget_catalog'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(table_exists, 1);
+
+        // `#[sql(default = "pending")]` shows up as `status`'s declared default.
+        let mut stmt = conn.prepare("PRAGMA table_info(widget_catalog)").unwrap();
+        let status_default: Option<String> = stmt
+            .query_map([], |row| {
+                let name: String = row.get(1)?;
+                let default: Option<String> = row.get(4)?;
+                Ok((name, default))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap()
+            .into_iter()
+            .find(|(name, _)| name == "status")
+            .and_then(|(_, default)| default);
+        assert_eq!(status_default.as_deref(), Some("'pending'"));
+    }
+
+    #[test]
+    fn generic_struct_derive_round_trips() {
+        let conn = Connection::open_in_memory().unwrap();
+        Tagged::<String>::create_table(&conn).unwrap();
+
+        let tagged = Tagged {
+            id: 0,
+            label: "gift".to_string(),
+        };
+        tagged.insert(&conn).unwrap();
+
+        let stored = Tagged::<String>::select_all(&conn).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].label, "gift");
+
+        let found = Tagged::<String>::find_by_id(&conn, stored[0].id).unwrap();
+        assert_eq!(found.map(|row| row.label), Some("gift".to_string()));
+    }
 }